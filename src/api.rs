@@ -0,0 +1,90 @@
+use axum::{
+    body::Bytes,
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use home_mng::Coap;
+
+use crate::devices;
+use crate::service_handler::HANDLERS;
+use crate::{get_service_name, SERVICE_DISCOVERY};
+
+#[derive(Debug, serde::Serialize)]
+struct ApiError {
+    error: String,
+}
+
+impl ApiError {
+    fn new(message: impl Into<String>) -> Self {
+        Self { error: message.into() }
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct ApiService {
+    id: String,
+    name: String,
+    service_type: Option<String>,
+}
+
+pub async fn list_services() -> Json<Vec<ApiService>> {
+    let mut services: Vec<_> = SERVICE_DISCOVERY
+        .all()
+        .into_iter()
+        .map(|(id, service_type, _addr)| {
+            let name = get_service_name(&id).unwrap_or(&id).to_string();
+            ApiService { id, name, service_type }
+        })
+        .collect();
+    services.sort_by(|a, b| (&a.name, &a.id).cmp(&(&b.name, &b.id)));
+    Json(services)
+}
+
+pub async fn get_service(Path(id): Path<String>) -> Response {
+    let (ser_type, addr) = match lookup_service(&id) {
+        Ok(found) => found,
+        Err(response) => return response,
+    };
+    let Some(handler) = HANDLERS.get(ser_type.as_str()) else {
+        return (StatusCode::NOT_FOUND, Json(ApiError::new("unknown service type"))).into_response();
+    };
+
+    let coap = Coap::new();
+    match handler.get_json(&id, &addr, &coap).await {
+        Ok(value) => Json(value).into_response(),
+        Err(e) => device_error_response(&e),
+    }
+}
+
+pub async fn put_service(Path(id): Path<String>, body: Bytes) -> Response {
+    let (ser_type, addr) = match lookup_service(&id) {
+        Ok(found) => found,
+        Err(response) => return response,
+    };
+    let Some(handler) = HANDLERS.get(ser_type.as_str()) else {
+        return (StatusCode::NOT_FOUND, Json(ApiError::new("unknown service type"))).into_response();
+    };
+
+    let coap = Coap::new();
+    match handler.put_json(&id, &addr, &coap, &body).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => device_error_response(&e),
+    }
+}
+
+fn lookup_service(id: &str) -> Result<(String, std::net::SocketAddr), Response> {
+    let Some((ser_type, addr)) = SERVICE_DISCOVERY.service(id) else {
+        return Err((StatusCode::NOT_FOUND, Json(ApiError::new("could not discover this service"))).into_response());
+    };
+    let Some(ser_type) = ser_type else {
+        return Err((StatusCode::NOT_FOUND, Json(ApiError::new("missing type for the discovered service"))).into_response());
+    };
+    Ok((ser_type, addr))
+}
+
+fn device_error_response(e: &devices::Error) -> Response {
+    (e.status_code(), Json(ApiError::new(e.to_string()))).into_response()
+}
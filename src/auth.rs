@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, SystemTime};
+
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use axum::{
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Form,
+};
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const CONFIG_PATH_ENV: &str = "HOME_WEB_AUTH_CONFIG";
+const DEFAULT_CONFIG_PATH: &str = "auth.toml";
+
+// Parses but never matches any real password; verified against for unknown usernames so
+// the response time doesn't leak which usernames exist.
+const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$AAECAwQFBgcICQoLDA0ODw$AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8";
+
+#[derive(serde::Deserialize)]
+struct Config {
+    session_secret: String,
+    #[serde(default)]
+    users: HashMap<String, String>,
+}
+
+fn load_config() -> Config {
+    let path = std::env::var(CONFIG_PATH_ENV).unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            println!("Failed to read auth config {}: {}", path, e);
+            ::std::process::exit(1);
+        }
+    };
+    match toml::from_str(&contents) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("Parsing error: {}", e);
+            ::std::process::exit(1);
+        }
+    }
+}
+
+pub struct Auth {
+    users: HashMap<String, String>,
+    session_secret: Vec<u8>,
+    sessions: Mutex<HashMap<String, SystemTime>>,
+}
+
+impl Auth {
+    const SESSION_TTL: Duration = Duration::from_secs(24 * 3600);
+    const SESSION_COOKIE: &'static str = "session";
+
+    fn new() -> Self {
+        let config = load_config();
+        Self {
+            users: config.users,
+            session_secret: config.session_secret.into_bytes(),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn verify_password(&self, username: &str, password: &str) -> bool {
+        let is_known_user = self.users.contains_key(username);
+        let hash = self.users.get(username).map(String::as_str).unwrap_or(DUMMY_PASSWORD_HASH);
+
+        let Ok(parsed_hash) = PasswordHash::new(hash) else {
+            return false;
+        };
+
+        let verified = Argon2::default()
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .is_ok();
+        is_known_user && verified
+    }
+
+    fn create_session(&self) -> String {
+        let mut id_bytes = [0u8; 32];
+        rand::thread_rng().fill(&mut id_bytes);
+        let id = hex_encode(&id_bytes);
+
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, created| created.elapsed().unwrap_or(Duration::MAX) < Self::SESSION_TTL);
+        sessions.insert(id.clone(), SystemTime::now());
+        id
+    }
+
+    fn remove_session(&self, id: &str) {
+        self.sessions.lock().unwrap().remove(id);
+    }
+
+    fn hmac_with_secret(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.session_secret).expect("HMAC accepts any key length")
+    }
+
+    fn sign(&self, id: &str) -> String {
+        let mut mac = self.hmac_with_secret();
+        mac.update(id.as_bytes());
+        format!("{}.{}", id, hex_encode(&mac.finalize().into_bytes()))
+    }
+
+    fn verify_cookie(&self, cookie_value: &str) -> bool {
+        let Some((id, tag_hex)) = cookie_value.split_once('.') else {
+            return false;
+        };
+        let Some(tag) = hex_decode(tag_hex) else {
+            return false;
+        };
+
+        let mut mac = self.hmac_with_secret();
+        mac.update(id.as_bytes());
+        if mac.verify_slice(&tag).is_err() {
+            return false;
+        }
+
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(id)
+            .is_some_and(|created| created.elapsed().unwrap_or(Duration::MAX) < Self::SESSION_TTL)
+    }
+}
+
+pub static AUTH: LazyLock<Auth> = LazyLock::new(Auth::new);
+
+#[derive(Debug, serde::Deserialize)]
+pub struct LoginForm {
+    username: String,
+    password: String,
+}
+
+pub async fn login(Form(form): Form<LoginForm>) -> Response {
+    if !AUTH.verify_password(&form.username, &form.password) {
+        return (StatusCode::UNAUTHORIZED, "Invalid credentials").into_response();
+    }
+
+    let session_id = AUTH.create_session();
+    let cookie = format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Strict",
+        Auth::SESSION_COOKIE,
+        AUTH.sign(&session_id)
+    );
+
+    (StatusCode::OK, [(axum::http::header::SET_COOKIE, cookie)]).into_response()
+}
+
+pub async fn logout(request: Request) -> Response {
+    if let Some(raw) = request
+        .headers()
+        .get(axum::http::header::COOKIE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(find_session_cookie)
+    {
+        if let Some((id, _)) = raw.split_once('.') {
+            AUTH.remove_session(id);
+        }
+    }
+
+    let cookie = format!("{}=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0", Auth::SESSION_COOKIE);
+    (StatusCode::OK, [(axum::http::header::SET_COOKIE, cookie)]).into_response()
+}
+
+pub async fn require_session(request: Request, next: Next) -> Response {
+    let authorized = request
+        .headers()
+        .get(axum::http::header::COOKIE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(find_session_cookie)
+        .is_some_and(|raw| AUTH.verify_cookie(&raw));
+
+    if authorized {
+        next.run(request).await
+    } else {
+        (StatusCode::UNAUTHORIZED, "Unauthorized").into_response()
+    }
+}
+
+fn find_session_cookie(cookies: &str) -> Option<String> {
+    let prefix = format!("{}=", Auth::SESSION_COOKIE);
+    cookies
+        .split(';')
+        .map(|part| part.trim())
+        .find_map(|part| part.strip_prefix(&prefix).map(|value| value.to_string()))
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hex_decode(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
@@ -0,0 +1,200 @@
+use std::net::SocketAddr;
+
+use axum::http::StatusCode;
+
+use home_mng::{Coap, Content};
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidResponse(home_mng::Error),
+    MissingContentType,
+    UnexpectedContentType,
+    UnexpectedCborElement,
+    InvalidRequest(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidResponse(e) => write!(f, "Invalid response: {}", e),
+            Error::MissingContentType => write!(f, "Missing content type"),
+            Error::UnexpectedContentType => write!(f, "Unexpected content type"),
+            Error::UnexpectedCborElement => write!(f, "Unexpected CBOR element"),
+            Error::InvalidRequest(e) => write!(f, "Invalid request: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Error::InvalidResponse(_)
+            | Error::MissingContentType
+            | Error::UnexpectedContentType
+            | Error::UnexpectedCborElement => StatusCode::BAD_GATEWAY,
+            Error::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RgbwState {
+    pub rgb: String,
+    pub w: u8,
+}
+
+impl RgbwState {
+    fn r(&self) -> u8 {
+        self.channel(0)
+    }
+    fn g(&self) -> u8 {
+        self.channel(1)
+    }
+    fn b(&self) -> u8 {
+        self.channel(2)
+    }
+
+    fn channel(&self, idx: usize) -> u8 {
+        let hex_str = self.rgb.trim_start_matches('#');
+        let offset_start = 2 * idx;
+        let offset_end = 2 * idx + 2;
+        let channel_value = &hex_str[offset_start..offset_end];
+        u8::from_str_radix(channel_value, 16).expect("Invalid rgbw value")
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ShcntState {
+    pub pos: u8,
+}
+
+pub async fn get_rgbw(coap: &Coap, addr: &SocketAddr, id: &str) -> Result<RgbwState, Error> {
+    let start = std::time::Instant::now();
+    let data = coap.get(addr, id, None).await;
+    crate::metrics::METRICS.observe_coap(id, "get", start, &data);
+    let data = extract_cbor_map_from_coap_response(data)?;
+
+    let mut rgb = "#".to_string();
+    for channel in ["r", "g", "b"] {
+        let value = cbor_map_get(&data, channel).ok_or(Error::UnexpectedCborElement)?;
+        let byte: u8 = value
+            .as_integer()
+            .unwrap()
+            .try_into()
+            .expect(&format!("Invalid parameter {} sent by {}", channel, id));
+        rgb += &format!("{:02x}", byte);
+    }
+
+    let w = cbor_map_get(&data, "w").ok_or(Error::UnexpectedCborElement)?;
+    let w: u8 = w
+        .as_integer()
+        .unwrap()
+        .try_into()
+        .expect(&format!("Invalid parameter w sent by {}", id));
+
+    Ok(RgbwState { rgb, w })
+}
+
+pub async fn set_rgbw(
+    coap: &Coap,
+    addr: &SocketAddr,
+    id: &str,
+    state: &RgbwState,
+) -> Result<(), Error> {
+    let payload_map = ciborium::value::Value::Map(
+        [
+            (
+                ciborium::value::Value::Text("r".to_string()),
+                ciborium::value::Value::Integer(state.r().into()),
+            ),
+            (
+                ciborium::value::Value::Text("g".to_string()),
+                ciborium::value::Value::Integer(state.g().into()),
+            ),
+            (
+                ciborium::value::Value::Text("b".to_string()),
+                ciborium::value::Value::Integer(state.b().into()),
+            ),
+            (
+                ciborium::value::Value::Text("w".to_string()),
+                ciborium::value::Value::Integer(state.w.into()),
+            ),
+            (
+                ciborium::value::Value::Text("d".to_string()),
+                ciborium::value::Value::Integer(3000.into()),
+            ),
+        ]
+        .to_vec(),
+    );
+
+    let start = std::time::Instant::now();
+    let result = coap.set(addr, id, &payload_map).await;
+    crate::metrics::METRICS.observe_coap(id, "set", start, &result);
+    result.map_err(Error::InvalidResponse)
+}
+
+pub async fn get_shcnt(coap: &Coap, addr: &SocketAddr, id: &str) -> Result<ShcntState, Error> {
+    let start = std::time::Instant::now();
+    let data = coap.get(addr, id, None).await;
+    crate::metrics::METRICS.observe_coap(id, "get", start, &data);
+    let data = extract_cbor_map_from_coap_response(data)?;
+
+    let pos = cbor_map_get(&data, "r").ok_or(Error::UnexpectedCborElement)?;
+    let pos: u8 = pos
+        .as_integer()
+        .unwrap()
+        .try_into()
+        .expect(&format!("Invalid parameter pos sent by {}", id));
+
+    Ok(ShcntState { pos })
+}
+
+pub async fn set_shcnt(
+    coap: &Coap,
+    addr: &SocketAddr,
+    id: &str,
+    state: &ShcntState,
+) -> Result<(), Error> {
+    let payload_map = ciborium::value::Value::Map(
+        [(
+            ciborium::value::Value::Text("val".to_string()),
+            ciborium::value::Value::Integer(state.pos.into()),
+        )]
+        .to_vec(),
+    );
+
+    let start = std::time::Instant::now();
+    let result = coap.set(addr, id, &payload_map).await;
+    crate::metrics::METRICS.observe_coap(id, "set", start, &result);
+    result.map_err(Error::InvalidResponse)
+}
+
+fn extract_cbor_map_from_coap_response(
+    response: Result<Option<Content>, std::io::Error>,
+) -> Result<Vec<(ciborium::Value, ciborium::Value)>, Error> {
+    let data = response.map_err(|e| Error::InvalidResponse(e))?;
+    let data = data.ok_or(Error::MissingContentType)?;
+    let Content::Cbor(data) = data else {
+        return Err(Error::UnexpectedContentType);
+    };
+    let ciborium::value::Value::Map(data) = data else {
+        return Err(Error::UnexpectedCborElement);
+    };
+    Ok(data)
+}
+
+fn cbor_map_get<'a>(
+    map: &'a Vec<(ciborium::value::Value, ciborium::value::Value)>,
+    key: &str,
+) -> Option<&'a ciborium::value::Value> {
+    for entry in map {
+        if let ciborium::value::Value::Text(entry_key) = &entry.0 {
+            if entry_key == key {
+                return Some(&entry.1);
+            }
+        }
+    }
+    None
+}
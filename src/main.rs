@@ -1,7 +1,11 @@
+mod api;
+mod auth;
+mod devices;
+mod metrics;
 mod service_discovery;
+mod service_handler;
 
 use std::collections::HashMap;
-use std::net::SocketAddr;
 use std::sync::LazyLock;
 
 use axum::{
@@ -9,6 +13,7 @@ use axum::{
         Path,
         Request,
     },
+    http::{Method, StatusCode},
     Form,
     response::Html,
     routing::{get, post},
@@ -16,31 +21,10 @@ use axum::{
     Router,
 };
 
-use home_mng::{Coap, Content};
+use home_mng::Coap;
 
 use tera::{Context, Tera};
 
-#[derive(Debug)]
-enum Error {
-    InvalidResponse(home_mng::Error),
-    MissingContentType,
-    UnexpectedContentType,
-    UnexpectedCborElement,
-}
-
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Error::InvalidResponse(e) => write!(f, "Invalid response: {}", e),
-            Error::MissingContentType => write!(f, "Missing content type"),
-            Error::UnexpectedContentType => write!(f, "Unexpected content type"),
-            Error::UnexpectedCborElement => write!(f, "Unexpected CBOR element"),
-        }
-    }
-}
-
-impl std::error::Error for Error {}
-
 static TERA: LazyLock<Tera> = LazyLock::new(|| {
     match Tera::new("templates/**/*.html") {
         Ok(t) => t,
@@ -85,7 +69,7 @@ static SERVICE_DISCOVERY: LazyLock<service_discovery::Proxy> = LazyLock::new(||
     service_discovery::Proxy::new()
 });
 
-fn get_service_name(key: &str) -> Option<&str> {
+pub(crate) fn get_service_name(key: &str) -> Option<&str> {
     SERVICE_NAMES.get(key).copied()
 }
 
@@ -107,159 +91,69 @@ async fn list_services() -> Html<String> {
     Html(TERA.render("services.html", &context).unwrap())
 }
 
-async fn service(Path(service_id): Path<String>, request: Request) -> Html<String> {
+async fn service(Path(service_id): Path<String>, request: Request) -> (StatusCode, Html<String>) {
     let service = if let Some(service) = SERVICE_DISCOVERY.service(&service_id) {
         service
     } else {
-        return Html(format!("Error: could not disocover this service"));
+        return (StatusCode::NOT_FOUND, Html(format!("Error: could not disocover this service")));
     };
     let id = &service_id;
     let name = get_service_name(id).unwrap_or(id);
     let ser_type = if let Some(ser_type) = &service.0 {
         ser_type
     } else {
-        return Html(format!("Error: missing type for the discovered service {}", id));
+        return (StatusCode::NOT_FOUND, Html(format!("Error: missing type for the discovered service {}", id)));
     };
     let addr = &service.1;
 
-    match ser_type.as_str() {
-        "rgbw" => service_rgbw(id, name, addr, request).await,
-        "shcnt" => service_shcnt(id, name, addr, request).await,
-        _ => return Html(format!("Error: unknown service type {}", ser_type)),
-    }
-}
-
-#[derive(Debug, serde::Deserialize)]
-struct Rgbw {
-    rgb: String,
-    w: u8,
-}
-
-impl Rgbw {
-    fn r(&self) -> u8 {
-        self.channel(0)
-    }
-    fn g(&self) -> u8 {
-        self.channel(1)
-    }
-    fn b(&self) -> u8 {
-        self.channel(2)
-    }
-
-    fn channel(&self, idx: usize) -> u8 {
-        let hex_str = self.rgb.trim_start_matches('#');
-        let offset_start = 2*idx;
-        let offset_end = 2*idx + 2;
-        let channel_value = &hex_str[offset_start..offset_end];
-        u8::from_str_radix(channel_value, 16).expect("Invalid rgbw value")
-    }
-}
-
-async fn service_rgbw(id: &str, name: &str, addr: &SocketAddr, request: Request) -> Html<String> {
-    let mut context = Context::new();
-    context.insert("name", name);
+    let Some(handler) = service_handler::HANDLERS.get(ser_type.as_str()) else {
+        return (StatusCode::NOT_FOUND, Html(format!("Error: unknown service type {}", ser_type)));
+    };
 
     let coap = Coap::new();
 
-    if let Ok(Form(rgbw)) = request.extract::<Form<Rgbw>, _>().await {
-        let payload_map = ciborium::value::Value::Map([
-                    (ciborium::value::Value::Text("r".to_string()), ciborium::value::Value::Integer(rgbw.r().into())),
-                    (ciborium::value::Value::Text("g".to_string()), ciborium::value::Value::Integer(rgbw.g().into())),
-                    (ciborium::value::Value::Text("b".to_string()), ciborium::value::Value::Integer(rgbw.b().into())),
-                    (ciborium::value::Value::Text("w".to_string()), ciborium::value::Value::Integer(rgbw.w.into())),
-                    (ciborium::value::Value::Text("d".to_string()), ciborium::value::Value::Integer(3000.into())),
-                ].to_vec());
-
-        let _ = coap.set(addr, id, &payload_map).await;
-        context.insert("rgb", &rgbw.rgb);
-        context.insert("w", &rgbw.w);
-    } else {
-        let data = coap.get(addr, id, None).await;
-
-        let data = match extract_cbor_map_from_coap_response(data) {
-            Ok(data) => data,
-            Err(e) => return Html(format!("Error: {} in message received from {}", e, id)),
-        };
-
-        let mut rgb = "#".to_string();
-        for channel in ["r", "g", "b"] {
-            if let Some(value) = cbor_map_get(&data, channel) {
-                let byte: u8 = value.as_integer().unwrap().try_into().expect(&format!("Invalid parameter {} sent by {}", channel, id));
-                rgb += &format!("{:02x}", byte);
-            } else {
-                return Html(format!("Error: Missing value for parameter {} sent by {}", channel, id));
+    if request.method() == Method::POST {
+        if let Ok(Form(form)) = request.extract::<Form<HashMap<String, String>>, _>().await {
+            if let Err(e) = handler.apply(id, addr, &coap, &form).await {
+                return (e.status_code(), Html(format!("Error: {} in message sent to {}", e, id)));
             }
         }
-
-        context.insert("rgb", &rgb);
-
-        if let Some(value) = cbor_map_get(&data, "w") {
-            context.insert("w", value);
-        } else {
-            return Html(format!("Error: Missing value for parameter \"w\" sent by {}", id));
-        }
     }
 
-    Html(TERA.render("services/rgbw.html", &context).unwrap())
+    match handler.render(id, name, addr, &coap).await {
+        Ok(html) => (StatusCode::OK, html),
+        Err(e) => (e.status_code(), Html(format!("Error: {} in message received from {}", e, id))),
+    }
 }
 
-#[derive(Debug, serde::Deserialize)]
-struct Shcnt {
-    pos: u8,
+async fn metrics_handler() -> String {
+    metrics::METRICS.encode()
 }
 
-async fn service_shcnt(id: &str, name: &str, addr: &SocketAddr, request: Request) -> Html<String> {
-    let mut context = Context::new();
-    context.insert("name", name);
-
-    let coap = Coap::new();
-
-    if let Ok(Form(shcnt)) = request.extract::<Form<Shcnt>, _>().await {
-        let payload_map = ciborium::value::Value::Map([
-                    (ciborium::value::Value::Text("val".to_string()), ciborium::value::Value::Integer(shcnt.pos.into())),
-                ].to_vec());
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
 
-        let _ = coap.set(addr, id, &payload_map).await;
-        context.insert("pos", &shcnt.pos);
-    } else {
-        let data = coap.get(addr, id, None).await;
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
 
-        let data = match extract_cbor_map_from_coap_response(data) {
-            Ok(data) => data,
-            Err(e) => return Html(format!("Error: {} in message received from {}", e, id)),
-        };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
 
-        if let Some(value) = cbor_map_get(&data, "r") {
-            context.insert("pos", value);
-        } else {
-            return Html(format!("Error: Missing value for parameter \"pos\" sent by {}", id));
-        }
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
     }
 
-    Html(TERA.render("services/shcnt.html", &context).unwrap())
-}
-
-fn extract_cbor_map_from_coap_response(response: Result<Option<Content>, std::io::Error>) -> Result<Vec<(ciborium::Value, ciborium::Value)>, Error> {
-    let data = response.map_err(|e| Error::InvalidResponse(e))?; 
-    let data = data.ok_or(Error::MissingContentType)?;
-    let Content::Cbor(data) = data else {
-        return Err(Error::UnexpectedContentType);
-    };
-    let ciborium::value::Value::Map(data) = data else {
-        return Err(Error::UnexpectedCborElement);
-    };
-    Ok(data)
-}
-
-fn cbor_map_get<'a>(map: &'a Vec<(ciborium::value::Value, ciborium::value::Value)>, key: &str) -> Option<&'a ciborium::value::Value> {
-    for entry in map {
-        if let ciborium::value::Value::Text(entry_key) = &entry.0 {
-            if entry_key == key {
-                return Some(&entry.1);
-            }
-        }
-    }
-    None
+    SERVICE_DISCOVERY.shutdown().await;
 }
 
 #[tokio::main]
@@ -269,9 +163,25 @@ async fn main() {
     let app = Router::new()
         .route("/", get(index))
         .route("/service/:id", get(service))
-        .route("/service/:id", post(service))
-        .route("/services", get(list_services));
+        .route(
+            "/service/:id",
+            post(service).route_layer(axum::middleware::from_fn(auth::require_session)),
+        )
+        .route("/services", get(list_services))
+        .route("/metrics", get(metrics_handler))
+        .route("/login", post(auth::login))
+        .route("/logout", post(auth::logout))
+        .route("/api/services", get(api::list_services))
+        .route("/api/service/:id", get(api::get_service))
+        .route(
+            "/api/service/:id",
+            axum::routing::put(api::put_service)
+                .route_layer(axum::middleware::from_fn(auth::require_session)),
+        );
 
     let listener = tokio::net::TcpListener::bind("[::0]:3000").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .unwrap();
 }
@@ -0,0 +1,87 @@
+use std::sync::LazyLock;
+
+use prometheus::{
+    register_counter_vec_with_registry, register_counter_with_registry,
+    register_gauge_with_registry, register_histogram_vec_with_registry, Counter, CounterVec,
+    Gauge, HistogramVec, Registry, TextEncoder,
+};
+
+pub struct Metrics {
+    registry: Registry,
+    pub discovered_services: Gauge,
+    pub evicted_services: Counter,
+    pub coap_requests: CounterVec,
+    pub coap_latency: HistogramVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+
+        let discovered_services = register_gauge_with_registry!(
+            "home_web_discovered_services",
+            "Number of services currently present in the discovery map",
+            registry
+        )
+        .unwrap();
+
+        let evicted_services = register_counter_with_registry!(
+            "home_web_evicted_services_total",
+            "Number of services evicted by the discovery cleanup thread",
+            registry
+        )
+        .unwrap();
+
+        let coap_requests = register_counter_vec_with_registry!(
+            "home_web_coap_requests_total",
+            "CoAP get/set calls, labelled by service id, method and outcome",
+            &["service", "method", "outcome"],
+            registry
+        )
+        .unwrap();
+
+        let coap_latency = register_histogram_vec_with_registry!(
+            "home_web_coap_request_duration_seconds",
+            "CoAP get/set latency in seconds, labelled by service id and method",
+            &["service", "method"],
+            registry
+        )
+        .unwrap();
+
+        Self {
+            registry,
+            discovered_services,
+            evicted_services,
+            coap_requests,
+            coap_latency,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let encoder = TextEncoder::new();
+        let metric_families = self.registry.gather();
+        encoder.encode_to_string(&metric_families).unwrap_or_default()
+    }
+
+    pub fn observe_coap<T>(
+        &self,
+        service: &str,
+        method: &str,
+        start: std::time::Instant,
+        result: &Result<T, std::io::Error>,
+    ) {
+        let outcome = match result {
+            Ok(_) => "ok",
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => "timeout",
+            Err(_) => "error",
+        };
+        self.coap_requests
+            .with_label_values(&[service, method, outcome])
+            .inc();
+        self.coap_latency
+            .with_label_values(&[service, method])
+            .observe(start.elapsed().as_secs_f64());
+    }
+}
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
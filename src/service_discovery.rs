@@ -1,20 +1,31 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
 use std::time::SystemTime;
 
-use tokio::time::{sleep, Duration};
+use futures_util::StreamExt;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout, Duration};
+use tokio_util::sync::CancellationToken;
 
 use home_mng::Coap;
 
+use crate::metrics::METRICS;
+
 struct ProxyEntry {
     update_timestamp: SystemTime,
     service_type: Option<String>,
     address: SocketAddr,
+    observe_seq: Option<u32>,
 }
 
 pub struct Proxy {
     services: Arc<Mutex<HashMap<String, ProxyEntry>>>,
+    // Names that already have an `observe_thread` running. Pruned by `cleanup_thread`
+    // on eviction so a service that drops off and is rediscovered gets a fresh one.
+    observed: Mutex<HashSet<String>>,
+    shutdown: CancellationToken,
+    handles: Mutex<Vec<JoinHandle<()>>>,
 }
 
 impl Proxy {
@@ -22,46 +33,164 @@ impl Proxy {
     const CLEANUP_PERIOD: Duration = Self::DISCOVERY_PERIOD;
     const CLEANUP_INITIAL_DELAY: Duration = Duration::from_secs(30);
     const CLEANUP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3600);
+    const OBSERVE_REGISTER_RETRY_DELAY: Duration = Duration::from_secs(30);
+    const OBSERVE_SEQ_MODULUS: u32 = 1 << 24;
 
     pub fn new() -> Self {
         Self {
             services: Arc::new(Mutex::new(HashMap::new())),
+            observed: Mutex::new(HashSet::new()),
+            shutdown: CancellationToken::new(),
+            handles: Mutex::new(Vec::new()),
         }
     }
 
     pub async fn run(&'static self) {
-        tokio::spawn(self.discovery_thread());
-        tokio::spawn(self.cleanup_thread());
+        let discovery = tokio::spawn(self.discovery_thread());
+        let cleanup = tokio::spawn(self.cleanup_thread());
+        self.handles.lock().unwrap().extend([discovery, cleanup]);
+    }
+
+    pub async fn shutdown(&self) {
+        self.shutdown.cancel();
+
+        loop {
+            let handles: Vec<_> = self.handles.lock().unwrap().drain(..).collect();
+            if handles.is_empty() {
+                break;
+            }
+            for handle in handles {
+                let _ = handle.await;
+            }
+        }
     }
 
-    async fn discovery_thread(&self) {
+    async fn discovery_thread(&'static self) {
         let coap = Coap::new();
 
         loop {
-            let services = coap.service_discovery(None, None).await.unwrap();
-            
+            let services = tokio::select! {
+                _ = self.shutdown.cancelled() => return,
+                services = coap.service_discovery(None, None) => services.unwrap(),
+            };
+
             for service in services {
+                let name = service.0.clone();
                 self.services.lock().unwrap().insert(service.0, ProxyEntry {
                     update_timestamp: SystemTime::now(),
                     service_type: service.1,
                     address: service.2,
+                    observe_seq: None,
                 });
+
+                if self.observed.lock().unwrap().insert(name.clone()) {
+                    let handle = tokio::spawn(self.observe_thread(name));
+                    self.handles.lock().unwrap().push(handle);
+                }
             }
 
-            sleep(Self::DISCOVERY_PERIOD).await;
+            METRICS
+                .discovered_services
+                .set(self.services.lock().unwrap().len() as f64);
+
+            tokio::select! {
+                _ = self.shutdown.cancelled() => return,
+                _ = sleep(Self::DISCOVERY_PERIOD) => {},
+            }
         }
     }
 
+    async fn observe_thread(&self, name: String) {
+        loop {
+            if self.shutdown.is_cancelled() {
+                return;
+            }
+
+            let addr = match self.services.lock().unwrap().get(&name) {
+                Some(entry) => entry.address,
+                None => return,
+            };
+
+            let coap = Coap::new();
+            let mut notifications = match coap.observe(&addr, &name).await {
+                Ok(notifications) => notifications,
+                Err(_) => {
+                    tokio::select! {
+                        _ = self.shutdown.cancelled() => return,
+                        _ = sleep(Self::OBSERVE_REGISTER_RETRY_DELAY) => {},
+                    }
+                    continue;
+                }
+            };
+
+            loop {
+                let next = tokio::select! {
+                    _ = self.shutdown.cancelled() => return,
+                    next = timeout(Self::CLEANUP_TIMEOUT, notifications.next()) => next,
+                };
+                let Ok(Some(Ok((seq, service_type)))) = next else {
+                    break;
+                };
+
+                let mut services = self.services.lock().unwrap();
+                let Some(entry) = services.get_mut(&name) else {
+                    return;
+                };
+
+                let is_fresh = entry
+                    .observe_seq
+                    .map_or(true, |last| Self::observe_seq_is_newer(seq, last));
+                if is_fresh {
+                    entry.observe_seq = Some(seq);
+                    entry.service_type = service_type;
+                    entry.update_timestamp = SystemTime::now();
+                }
+            }
+        }
+    }
+
+    // RFC 7641 §3.4: `new` is newer than `old` iff `(new - old) mod 2^24` falls in `1..2^23`.
+    fn observe_seq_is_newer(new: u32, old: u32) -> bool {
+        let diff = new.wrapping_sub(old) % Self::OBSERVE_SEQ_MODULUS;
+        (1..(Self::OBSERVE_SEQ_MODULUS / 2)).contains(&diff)
+    }
+
     async fn cleanup_thread(&self) {
-        sleep(Self::CLEANUP_INITIAL_DELAY).await;
+        tokio::select! {
+            _ = self.shutdown.cancelled() => return,
+            _ = sleep(Self::CLEANUP_INITIAL_DELAY) => {},
+        }
 
         loop {
-            self.services.lock().unwrap().retain(
-                    |_, v| v.update_timestamp.elapsed()
-                            .unwrap_or(std::time::Duration::ZERO)
-                            < Self::CLEANUP_TIMEOUT
-                );
-            sleep(Self::CLEANUP_PERIOD).await;
+            let mut services = self.services.lock().unwrap();
+            let is_live = |v: &ProxyEntry| {
+                v.update_timestamp.elapsed().unwrap_or(std::time::Duration::ZERO) < Self::CLEANUP_TIMEOUT
+            };
+            let evicted_names: Vec<String> = services
+                .iter()
+                .filter(|(_, v)| !is_live(v))
+                .map(|(name, _)| name.clone())
+                .collect();
+            services.retain(|_, v| is_live(v));
+            let evicted = evicted_names.len();
+            drop(services);
+
+            if !evicted_names.is_empty() {
+                let mut observed = self.observed.lock().unwrap();
+                for name in &evicted_names {
+                    observed.remove(name);
+                }
+            }
+
+            METRICS.evicted_services.inc_by(evicted as f64);
+            METRICS
+                .discovered_services
+                .set(self.services.lock().unwrap().len() as f64);
+
+            tokio::select! {
+                _ = self.shutdown.cancelled() => return,
+                _ = sleep(Self::CLEANUP_PERIOD) => {},
+            }
         }
     }
 
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::LazyLock;
+
+use async_trait::async_trait;
+use axum::response::Html;
+use tera::Context;
+
+use home_mng::Coap;
+
+use crate::devices::{self, Error, RgbwState, ShcntState};
+use crate::TERA;
+
+#[async_trait]
+pub trait ServiceHandler: Send + Sync {
+    fn type_id(&self) -> &'static str;
+
+    async fn render(&self, id: &str, name: &str, addr: &SocketAddr, coap: &Coap) -> Result<Html<String>, Error>;
+    async fn apply(&self, id: &str, addr: &SocketAddr, coap: &Coap, form: &HashMap<String, String>) -> Result<(), Error>;
+
+    async fn get_json(&self, id: &str, addr: &SocketAddr, coap: &Coap) -> Result<serde_json::Value, Error>;
+    async fn put_json(&self, id: &str, addr: &SocketAddr, coap: &Coap, body: &[u8]) -> Result<(), Error>;
+}
+
+fn form_field(form: &HashMap<String, String>, field: &str) -> Result<String, Error> {
+    form.get(field)
+        .cloned()
+        .ok_or_else(|| Error::InvalidRequest(format!("missing field \"{}\"", field)))
+}
+
+struct RgbwHandler;
+
+#[async_trait]
+impl ServiceHandler for RgbwHandler {
+    fn type_id(&self) -> &'static str {
+        "rgbw"
+    }
+
+    async fn render(&self, id: &str, name: &str, addr: &SocketAddr, coap: &Coap) -> Result<Html<String>, Error> {
+        let state = devices::get_rgbw(coap, addr, id).await?;
+
+        let mut context = Context::new();
+        context.insert("name", name);
+        context.insert("rgb", &state.rgb);
+        context.insert("w", &state.w);
+        Ok(Html(TERA.render("services/rgbw.html", &context).unwrap()))
+    }
+
+    async fn apply(&self, id: &str, addr: &SocketAddr, coap: &Coap, form: &HashMap<String, String>) -> Result<(), Error> {
+        let rgb = form_field(form, "rgb")?;
+        let w = form_field(form, "w")?
+            .parse()
+            .map_err(|_| Error::InvalidRequest("field \"w\" must be a number 0-255".to_string()))?;
+
+        devices::set_rgbw(coap, addr, id, &RgbwState { rgb, w }).await
+    }
+
+    async fn get_json(&self, id: &str, addr: &SocketAddr, coap: &Coap) -> Result<serde_json::Value, Error> {
+        let state = devices::get_rgbw(coap, addr, id).await?;
+        Ok(serde_json::to_value(state).expect("RgbwState always serializes"))
+    }
+
+    async fn put_json(&self, id: &str, addr: &SocketAddr, coap: &Coap, body: &[u8]) -> Result<(), Error> {
+        let state: RgbwState = serde_json::from_slice(body)
+            .map_err(|e| Error::InvalidRequest(format!("invalid request body: {}", e)))?;
+        devices::set_rgbw(coap, addr, id, &state).await
+    }
+}
+
+struct ShcntHandler;
+
+#[async_trait]
+impl ServiceHandler for ShcntHandler {
+    fn type_id(&self) -> &'static str {
+        "shcnt"
+    }
+
+    async fn render(&self, id: &str, name: &str, addr: &SocketAddr, coap: &Coap) -> Result<Html<String>, Error> {
+        let state = devices::get_shcnt(coap, addr, id).await?;
+
+        let mut context = Context::new();
+        context.insert("name", name);
+        context.insert("pos", &state.pos);
+        Ok(Html(TERA.render("services/shcnt.html", &context).unwrap()))
+    }
+
+    async fn apply(&self, id: &str, addr: &SocketAddr, coap: &Coap, form: &HashMap<String, String>) -> Result<(), Error> {
+        let pos = form_field(form, "pos")?
+            .parse()
+            .map_err(|_| Error::InvalidRequest("field \"pos\" must be a number 0-255".to_string()))?;
+
+        devices::set_shcnt(coap, addr, id, &ShcntState { pos }).await
+    }
+
+    async fn get_json(&self, id: &str, addr: &SocketAddr, coap: &Coap) -> Result<serde_json::Value, Error> {
+        let state = devices::get_shcnt(coap, addr, id).await?;
+        Ok(serde_json::to_value(state).expect("ShcntState always serializes"))
+    }
+
+    async fn put_json(&self, id: &str, addr: &SocketAddr, coap: &Coap, body: &[u8]) -> Result<(), Error> {
+        let state: ShcntState = serde_json::from_slice(body)
+            .map_err(|e| Error::InvalidRequest(format!("invalid request body: {}", e)))?;
+        devices::set_shcnt(coap, addr, id, &state).await
+    }
+}
+
+pub static HANDLERS: LazyLock<HashMap<&'static str, Box<dyn ServiceHandler>>> = LazyLock::new(|| {
+    let handlers: Vec<Box<dyn ServiceHandler>> = vec![Box::new(RgbwHandler), Box::new(ShcntHandler)];
+    handlers.into_iter().map(|handler| (handler.type_id(), handler)).collect()
+});